@@ -1,23 +1,30 @@
 use lyon::geom::euclid::Size2D;
-use lyon::geom::LineSegment;
-use lyon::path::math::{point, Point};
+use lyon::geom::{CubicBezierSegment, LineSegment, QuadraticBezierSegment};
+use lyon::path::math::{point, Point, Vector};
 use lyon::path::traits::PathBuilder;
+use lyon::path::path::Builder;
 use lyon::path::{Path, PathEvent, Winding};
+use std::collections::VecDeque;
 
 #[derive(Clone, Debug, PartialEq)]
 #[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 struct DashOptions {
     pub initial_offset: f32,
     pub array: Vec<f32>,
+    /// Maximum distance between the curve and its flattened approximation,
+    /// used to flatten `Quadratic`/`Cubic` path events before dashing them.
+    pub tolerance: f32,
 }
 
 impl DashOptions {
-    pub fn new(initial_offset: f32, array: Vec<f32>) -> Self {
+    pub fn new(initial_offset: f32, array: Vec<f32>, tolerance: f32) -> Self {
         assert!(!array.is_empty());
-        assert_eq!(array.iter().enumerate().find(|(_, &x)| x <= 0.0), None);
+        assert_eq!(array.iter().enumerate().find(|(_, &x)| x < 0.0), None);
+        assert!(tolerance > 0.0);
         DashOptions {
             initial_offset,
             array,
+            tolerance,
         }
     }
 }
@@ -49,16 +56,21 @@ struct DashAction {
 
 impl DashCursor {
     pub fn new(options: &DashOptions) -> Self {
-        // TODO magic: duplicate if odd (needed?)
-        // TODO magic: remove zeroes
-        let cumulative_array = DashCursor::cumulate_array(&options.array);
-        let current_offset = options
-            .initial_offset
-            .rem_euclid(*cumulative_array.last().unwrap()); // TODO does this work for negative offsets?
+        let array = DashCursor::normalize_array(&options.array);
+        let cumulative_array = DashCursor::cumulate_array(&array);
+        // rem_euclid brings any offset, however large or negative, into
+        // [0, cycle length]. For a negative offset much smaller in magnitude
+        // than the cycle it can round up to exactly the cycle length, which
+        // is the same point of the pattern as 0, so it is wrapped to 0.
+        let cycle_length = *cumulative_array.last().unwrap();
+        let mut current_offset = options.initial_offset.rem_euclid(cycle_length);
+        if current_offset >= cycle_length {
+            current_offset = 0.0;
+        }
         let current_index =
             DashCursor::find_index_in_cumulative_array(current_offset, &cumulative_array);
         DashCursor {
-            array: options.array.clone(),
+            array,
             cumulative_array,
             initial_offset: current_offset,
             initial_index: current_index,
@@ -72,6 +84,24 @@ impl DashCursor {
         self.current_index = self.initial_index;
     }
 
+    // SVG/Skia-compatible normalization: zero-length entries are dropped
+    // first (this shifts the on/off role of every entry after them, e.g.
+    // `[1, 0, 2]` becomes dash 1, gap 2), and if the
+    // resulting array has an odd length it is duplicated so on/off pairing
+    // is well defined, e.g. `[a, b, c]` -> `[a, b, c, a, b, c]`. If nothing
+    // is left, the whole array canceled out and the stroke is solid.
+    fn normalize_array(array: &[f32]) -> Vec<f32> {
+        let mut normalized: Vec<f32> = array.iter().copied().filter(|&x| x > 0.0).collect();
+        if normalized.is_empty() {
+            return vec![f32::MAX];
+        }
+        if normalized.len() % 2 == 1 {
+            let duplicated = normalized.clone();
+            normalized.extend(duplicated);
+        }
+        normalized
+    }
+
     fn cumulate_array(array: &[f32]) -> Vec<f32> {
         array
             .iter()
@@ -83,15 +113,14 @@ impl DashCursor {
     }
 
     fn find_index_in_cumulative_array(offset: f32, cumulative_array: &[f32]) -> usize {
-        let mut current_index = 0;
-        for &x in cumulative_array {
+        for (index, &x) in cumulative_array.iter().enumerate() {
             if x > offset {
-                break;
+                return index;
             }
-            current_index += 1;
         }
-        assert!(current_index < cumulative_array.len()); // TODO make numerically more stable by using the last element?
-        current_index
+        // The offset landed exactly on (or, due to floating point rounding,
+        // just past) the end of the cycle: wrap around to the start.
+        0
     }
 
     fn make_dash_action_type(index: usize) -> DashActionType {
@@ -162,6 +191,7 @@ mod tests {
                 let options = DashOptions::new(
                     0.05 - (phase as f32) * factor * 16.0,
                     vec![factor * 10.0, factor * 1.0, factor * 2.0, factor * 3.0],
+                    0.1,
                 );
                 let cursor = DashCursor::new(&options);
                 assert_slice_approx_eq(
@@ -174,7 +204,11 @@ mod tests {
                     &cursor.cumulative_array,
                     f32::EPSILON,
                 );
-                assert_approx_eq(0.05, cursor.current_offset, 0.000000001);
+                // `rem_euclid` on an offset this far from the cycle (up to
+                // magnitude 32 here) loses more than the 1e-9 we use for
+                // dash lengths elsewhere in this file; 1e-4 is still
+                // tight relative to the dash lengths involved (0.01-10 units).
+                assert_approx_eq(0.05, cursor.current_offset, 0.0001);
                 assert_eq!(0, cursor.current_index);
             }
         }
@@ -208,7 +242,7 @@ mod tests {
 
     #[test]
     fn test_no_segment_cross() {
-        let options = DashOptions::new(0.0, vec![1.0, 2.0]);
+        let options = DashOptions::new(0.0, vec![1.0, 2.0], 0.1);
         let mut cursor = DashCursor::new(&options);
         let action = &cursor.progress_by(0.5);
         assert_action_eq(&make_dash(0.5, 0.0), action);
@@ -216,13 +250,58 @@ mod tests {
 
     #[test]
     fn test_segment_cross() {
-        let options = DashOptions::new(0.0, vec![1.0, 2.0]);
+        let options = DashOptions::new(0.0, vec![1.0, 2.0], 0.1);
         let mut cursor = DashCursor::new(&options);
         let action = cursor.progress_by(1.5);
         assert_action_eq(&make_dash(1.0, 0.5), &action);
         let action = cursor.progress_by(action.remaining_distance);
         assert_action_eq(&make_gap(0.5, 0.0), &action);
     }
+
+    #[test]
+    fn test_odd_length_array_is_duplicated() {
+        let options = DashOptions::new(0.0, vec![1.0, 2.0, 3.0], 0.1);
+        let cursor = DashCursor::new(&options);
+        assert_slice_approx_eq(&[1.0, 2.0, 3.0, 1.0, 2.0, 3.0], &cursor.array, f32::EPSILON);
+        assert_slice_approx_eq(
+            &[1.0, 3.0, 6.0, 7.0, 9.0, 12.0],
+            &cursor.cumulative_array,
+            f32::EPSILON,
+        );
+    }
+
+    #[test]
+    fn test_zero_length_entries_are_dropped() {
+        let options = DashOptions::new(0.0, vec![1.0, 0.0, 2.0], 0.1);
+        let cursor = DashCursor::new(&options);
+        assert_slice_approx_eq(&[1.0, 2.0], &cursor.array, f32::EPSILON);
+    }
+
+    #[test]
+    fn test_all_zero_array_is_solid() {
+        let options = DashOptions::new(0.0, vec![0.0, 0.0], 0.1);
+        let mut cursor = DashCursor::new(&options);
+        let action = cursor.progress_by(1_000_000.0);
+        assert_action_eq(&make_dash(1_000_000.0, 0.0), &action);
+    }
+
+    #[test]
+    fn test_large_negative_initial_offset() {
+        let options = DashOptions::new(-1_000_000.0, vec![1.0, 2.0], 0.1);
+        let cursor = DashCursor::new(&options);
+        assert!(cursor.current_offset >= 0.0 && cursor.current_offset < 3.0);
+    }
+
+    #[test]
+    fn test_offset_on_cumulative_array_boundary_wraps_to_zero() {
+        let options = DashOptions::new(0.0, vec![1.0, 2.0], 0.1);
+        let cumulative_array = DashCursor::new(&options).cumulative_array;
+        let index = DashCursor::find_index_in_cumulative_array(
+            *cumulative_array.last().unwrap(),
+            &cumulative_array,
+        );
+        assert_eq!(0, index);
+    }
 }
 
 #[derive(Debug)]
@@ -236,27 +315,43 @@ enum DashOrGap {
         // TODO squeeze gaps?
         distance: f32,
     },
+    // Marks a sub-path boundary on the source path (a new `Begin`, or an
+    // open `End`), independent of where the dash pattern itself is. Unlike
+    // `Gap`, this never gets squeezed away: two sub-paths must never be
+    // stroked or filled as if they were one contour, even if the dash
+    // pattern would otherwise bridge them.
+    ContourBreak,
 }
 
-struct FlattenedEventIterator {
+// Wraps an inner `PathEvent` iterator and lazily yields the dashed
+// `DashOrGap` segments of the path it describes. `DashCursor` state is
+// carried across `Line`/`End` events so a dash can span several of them;
+// it is only reset on `Begin`.
+struct FlattenedEventIterator<I: Iterator<Item = PathEvent>> {
+    inner: I,
     cursor: DashCursor,
+    tolerance: f32,
+    // Line segments produced by flattening a `Quadratic`/`Cubic` event,
+    // still waiting to be dashed. `DashCursor` state carries across them
+    // just like it does across separate `Line` events.
+    pending_lines: VecDeque<LineSegment<f32>>,
     line: LineSegment<f32>,
     line_length: f32,
     current_relative_distance: f32,
     remaining_distance: f32,
+    // Whether we've already seen a `Begin`. The first one doesn't end a
+    // previous sub-path, since there isn't one yet; every one after that
+    // does, and must be reported as a `DashOrGap::ContourBreak`.
+    started: bool,
 }
 
-// impl Iterator for FlattenedEventIterator {
-//     type Item = DashOrGap;
-//     fn next(&mut self) -> Option<Self::Item> {
-
-//     }
-// }
-
-impl FlattenedEventIterator {
-    pub fn new(options: &DashOptions) -> Self {
+impl<I: Iterator<Item = PathEvent>> FlattenedEventIterator<I> {
+    pub fn new(inner: I, options: &DashOptions) -> Self {
         FlattenedEventIterator {
+            inner,
             cursor: DashCursor::new(&options),
+            tolerance: options.tolerance,
+            pending_lines: VecDeque::new(),
             line: LineSegment {
                 from: Point::zero(),
                 to: Point::zero(),
@@ -264,6 +359,7 @@ impl FlattenedEventIterator {
             line_length: 0.0,
             current_relative_distance: 0.0,
             remaining_distance: 0.0,
+            started: false,
         }
     }
 
@@ -301,41 +397,537 @@ impl FlattenedEventIterator {
         }
     }
 
-    fn handle_line(&mut self, line: &LineSegment<f32>) {
-        self.initialize_line_loop(line);
-        while self.remaining_distance > 0.0f32 {
-            let output = self.inner_line_loop();
-            println!("Yield {:?}", output);
+    // Pulls `PathEvent`s from `inner` until there is a line to dash along
+    // or a sub-path boundary to report, and sets up the line loop in the
+    // former case. Returns `LineAdvance::Done` once `inner` is exhausted.
+    fn advance_to_next_line(&mut self) -> LineAdvance {
+        loop {
+            if let Some(line) = self.pending_lines.pop_front() {
+                self.initialize_line_loop(&line);
+                return LineAdvance::Line;
+            }
+            match self.inner.next() {
+                None => return LineAdvance::Done,
+                Some(PathEvent::Begin { .. }) => {
+                    let previous_subpath = self.started;
+                    self.cursor.reset();
+                    self.started = true;
+                    if previous_subpath {
+                        return LineAdvance::Break;
+                    }
+                }
+                Some(PathEvent::Line { from, to }) => {
+                    self.initialize_line_loop(&LineSegment { from, to });
+                    return LineAdvance::Line;
+                }
+                Some(PathEvent::End {
+                    last,
+                    first,
+                    close: true,
+                }) => {
+                    self.initialize_line_loop(&LineSegment {
+                        from: last,
+                        to: first,
+                    });
+                    return LineAdvance::Line;
+                }
+                Some(PathEvent::End { close: false, .. }) => {
+                    return LineAdvance::Break;
+                }
+                Some(PathEvent::Quadratic { from, ctrl, to }) => {
+                    let segment = QuadraticBezierSegment { from, ctrl, to };
+                    let tolerance = self.tolerance;
+                    let pending_lines = &mut self.pending_lines;
+                    let mut previous = from;
+                    segment.for_each_flattened(tolerance, &mut |point| {
+                        pending_lines.push_back(LineSegment {
+                            from: previous,
+                            to: point,
+                        });
+                        previous = point;
+                    });
+                }
+                Some(PathEvent::Cubic {
+                    from,
+                    ctrl1,
+                    ctrl2,
+                    to,
+                }) => {
+                    let segment = CubicBezierSegment {
+                        from,
+                        ctrl1,
+                        ctrl2,
+                        to,
+                    };
+                    let tolerance = self.tolerance;
+                    let pending_lines = &mut self.pending_lines;
+                    let mut previous = from;
+                    segment.for_each_flattened(tolerance, &mut |point| {
+                        pending_lines.push_back(LineSegment {
+                            from: previous,
+                            to: point,
+                        });
+                        previous = point;
+                    });
+                }
+            }
         }
     }
+}
+
+// What pulling more `PathEvent`s got us: a line ready to dash along, a
+// sub-path boundary to report before the next one, or nothing left.
+enum LineAdvance {
+    Line,
+    Break,
+    Done,
+}
+
+impl<I: Iterator<Item = PathEvent>> Iterator for FlattenedEventIterator<I> {
+    type Item = DashOrGap;
 
-    pub fn next_event(&mut self, event: PathEvent) -> () {
-        match event {
-            PathEvent::Begin { .. } => {
-                self.cursor.reset();
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.remaining_distance <= 0.0f32 {
+            match self.advance_to_next_line() {
+                LineAdvance::Line => {}
+                LineAdvance::Break => return Some(DashOrGap::ContourBreak),
+                LineAdvance::Done => return None,
             }
-            PathEvent::Line { from, to } => {
-                self.handle_line(&LineSegment { from, to });
+        }
+        Some(self.inner_line_loop())
+    }
+}
+
+// Consumes a stream of `DashOrGap` segments and assembles it into a `Path`.
+// Each `Dash` extends the current open sub-path with a `line_to`; each
+// `Gap` or `ContourBreak` ends it, so the next dash starts a fresh `begin`.
+// Dashes that pick up exactly where the previous one left off (the common
+// case: they came from the same line, or from consecutive chords of a
+// flattened curve) therefore stay in a single sub-path instead of
+// fragmenting. A `ContourBreak` additionally guarantees that two sub-paths
+// of the source path are never fused into one, even when the dash pattern
+// doesn't happen to put a gap at the boundary.
+struct DashToPath {
+    builder: Builder,
+    // Whether we are currently inside an open (unterminated) sub-path, i.e.
+    // whether the last `DashOrGap` we saw was a `Dash`.
+    is_open: bool,
+}
+
+impl DashToPath {
+    pub fn new() -> Self {
+        DashToPath {
+            builder: Path::builder(),
+            is_open: false,
+        }
+    }
+
+    pub fn push(&mut self, item: DashOrGap) {
+        match item {
+            DashOrGap::Dash { from, to, .. } => {
+                if !self.is_open {
+                    self.builder.begin(from);
+                    self.is_open = true;
+                }
+                self.builder.line_to(to);
             }
-            PathEvent::End {
-                last,
-                first,
-                close: true,
-            } => {
-                self.handle_line(&LineSegment {
-                    from: last,
-                    to: first,
-                });
+            DashOrGap::Gap { .. } | DashOrGap::ContourBreak => {
+                if self.is_open {
+                    self.builder.end(false);
+                    self.is_open = false;
+                }
             }
-            PathEvent::Quadratic { .. } => {
-                // TODO auto-flatten?
-                panic!("FlattenedEventIterator cannot handle quadratic path event!");
+        }
+    }
+
+    pub fn build(mut self) -> Path {
+        if self.is_open {
+            self.builder.end(false);
+        }
+        self.builder.build()
+    }
+
+    pub fn from_dashes<It: IntoIterator<Item = DashOrGap>>(dashes: It) -> Path {
+        let mut builder = DashToPath::new();
+        for item in dashes {
+            builder.push(item);
+        }
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod dash_to_path_tests {
+    use super::*;
+
+    #[test]
+    fn contour_break_starts_a_new_sub_path() {
+        // Two all-dash sub-paths with nothing but a `ContourBreak` between
+        // them must stay two sub-paths, not get fused into one `line_to`
+        // that bridges the gap between them.
+        let dashes = vec![
+            DashOrGap::Dash {
+                from: point(0.0, 0.0),
+                to: point(10.0, 0.0),
+                distance: 10.0,
+            },
+            DashOrGap::ContourBreak,
+            DashOrGap::Dash {
+                from: point(100.0, 100.0),
+                to: point(110.0, 100.0),
+                distance: 10.0,
+            },
+        ];
+        let path = DashToPath::from_dashes(dashes);
+        let begin_count = path
+            .iter()
+            .filter(|event| matches!(event, PathEvent::Begin { .. }))
+            .count();
+        assert_eq!(2, begin_count);
+        for event in path.iter() {
+            if let PathEvent::Line { from, to } = event {
+                assert!((to - from).length() <= 10.0);
             }
-            PathEvent::Cubic { .. } => {
-                // TODO auto-flatten?
-                panic!("FlattenedEventIterator cannot handle cubic path event!");
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LineCap {
+    Butt,
+    Square,
+    Round,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LineJoin {
+    Bevel,
+    Miter,
+    Round,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct StrokeStyle {
+    pub width: f32,
+    pub line_cap: LineCap,
+    pub line_join: LineJoin,
+}
+
+impl StrokeStyle {
+    pub fn new(width: f32, line_cap: LineCap, line_join: LineJoin) -> Self {
+        assert!(width > 0.0);
+        StrokeStyle {
+            width,
+            line_cap,
+            line_join,
+        }
+    }
+}
+
+// If a miter join would stick out further than this multiple of the
+// half-width, fall back to a bevel (mirrors SVG/Skia's default miter limit).
+const MITER_LIMIT: f32 = 4.0;
+
+// Samples the interior points of the arc of the given radius around
+// `center`, sweeping from `from` to `to` (both relative to `center`) on
+// whichever side bulges towards `outward` (its exact length doesn't
+// matter, only its direction). Excludes the two endpoints, which the
+// caller already has on hand.
+fn arc_points(center: Point, from: Vector, to: Vector, outward: Vector, radius: f32) -> Vec<Point> {
+    let two_pi = 2.0 * std::f32::consts::PI;
+    let angle_from = from.angle_from_x_axis().radians;
+    let angle_to = to.angle_from_x_axis().radians;
+
+    let mut sweep_ccw = angle_to - angle_from;
+    while sweep_ccw <= 0.0 {
+        sweep_ccw += two_pi;
+    }
+    let sweep_cw = sweep_ccw - two_pi;
+
+    let bulges_outward = |sweep: f32| {
+        let mid_angle = angle_from + sweep / 2.0;
+        Vector::new(mid_angle.cos(), mid_angle.sin()).dot(outward) > 0.0
+    };
+    let sweep = if bulges_outward(sweep_ccw) { sweep_ccw } else { sweep_cw };
+
+    let steps = ((sweep.abs() / (std::f32::consts::PI / 8.0)).ceil() as usize).max(1);
+    (1..steps)
+        .map(|i| {
+            let t = angle_from + sweep * (i as f32) / (steps as f32);
+            center + Vector::new(t.cos(), t.sin()) * radius
+        })
+        .collect()
+}
+
+// Intersects the two offset edges (through `p1`/`p2`, along `dir1`/`dir2`)
+// as infinite lines. Returns `None` (fall back to a bevel) when they are
+// near-parallel or the miter sticks out further than `MITER_LIMIT`.
+fn miter_point(p1: Point, dir1: Vector, p2: Point, dir2: Vector, half_width: f32) -> Option<Point> {
+    let denom = dir1.cross(dir2);
+    if denom.abs() < f32::EPSILON {
+        return None;
+    }
+    let t = (p2 - p1).cross(dir2) / denom;
+    let apex = p1 + dir1 * t;
+    if (apex - p1).length() > half_width * MITER_LIMIT {
+        return None;
+    }
+    Some(apex)
+}
+
+// The turn a polyline takes at one interior vertex, as seen by `join`.
+#[derive(Clone, Copy)]
+struct Corner {
+    vertex: Point,
+    dir_prev: Vector,
+    dir_next: Vector,
+}
+
+// Bridges the offset edge ending at `prev_end` (already the last point in
+// `out`) to the one starting at `next_start`, both on the same side (left
+// or right) of a polyline turning through `corner`.
+//
+// TODO: on the inner (concave) side of a sharp turn this can make the
+// offset edges cross themselves; a real tessellator would clip that.
+fn join(out: &mut Vec<Point>, corner: Corner, prev_end: Point, next_start: Point, half_width: f32, line_join: LineJoin) {
+    match line_join {
+        LineJoin::Bevel => {
+            out.push(next_start);
+        }
+        LineJoin::Miter => {
+            match miter_point(prev_end, corner.dir_prev, next_start, corner.dir_next, half_width) {
+                Some(apex) => out.push(apex),
+                None => out.push(next_start),
+            }
+        }
+        LineJoin::Round => {
+            let from = prev_end - corner.vertex;
+            let to = next_start - corner.vertex;
+            out.extend(arc_points(corner.vertex, from, to, from + to, half_width));
+            out.push(next_start);
+        }
+    }
+}
+
+// Returns the vertices (if any) that terminate an open dash end at `tip`,
+// to be inserted between the already-present left/right offset points
+// `from`/`to`. `outward` points away from the dash along its axis. A
+// `Butt` cap needs nothing extra: the straight edge from `from` to `to`
+// that the caller draws by just continuing the contour already is one.
+fn cap_vertices(tip: Point, from: Point, to: Point, half_width: f32, outward: Vector, line_cap: LineCap) -> Vec<Point> {
+    match line_cap {
+        LineCap::Butt => Vec::new(),
+        LineCap::Square => vec![from + outward * half_width, to + outward * half_width],
+        LineCap::Round => arc_points(tip, from - tip, to - tip, outward, half_width),
+    }
+}
+
+// Offsets a single dash polyline by `style.width / 2` on each side and
+// closes it into a fillable contour, honoring `style.line_join` at the
+// interior vertices and `style.line_cap` at both open ends.
+fn stroke_polyline(points: &[Point], style: &StrokeStyle, builder: &mut Builder) {
+    assert!(points.len() >= 2);
+    let half_width = style.width / 2.0;
+
+    // Coincident consecutive points (a zero-length dash, or a duplicate
+    // flattened point) have no direction to offset along; `.normalize()`
+    // on a zero vector is NaN and would poison the whole contour. Drop
+    // them before computing directions.
+    let mut points: Vec<Point> = points.to_vec();
+    points.dedup_by(|a, b| (*a - *b).length() <= f32::EPSILON);
+    if points.len() < 2 {
+        return;
+    }
+    let points = &points[..];
+
+    let directions: Vec<Vector> = (0..points.len() - 1)
+        .map(|i| (points[i + 1] - points[i]).normalize())
+        .collect();
+
+    let mut left = Vec::new();
+    let mut right = Vec::new();
+
+    for i in 0..directions.len() {
+        let normal = Vector::new(-directions[i].y, directions[i].x) * half_width;
+        let seg_left_start = points[i] + normal;
+        let seg_left_end = points[i + 1] + normal;
+        let seg_right_start = points[i] - normal;
+        let seg_right_end = points[i + 1] - normal;
+
+        if i == 0 {
+            left.push(seg_left_start);
+            right.push(seg_right_start);
+        } else {
+            let corner = Corner {
+                vertex: points[i],
+                dir_prev: directions[i - 1],
+                dir_next: directions[i],
+            };
+            let left_prev_end = *left.last().unwrap();
+            join(&mut left, corner, left_prev_end, seg_left_start, half_width, style.line_join);
+            let right_prev_end = *right.last().unwrap();
+            join(&mut right, corner, right_prev_end, seg_right_start, half_width, style.line_join);
+        }
+        left.push(seg_left_end);
+        right.push(seg_right_end);
+    }
+
+    let start = points[0];
+    let end = points[points.len() - 1];
+    let start_dir = directions[0];
+    let end_dir = directions[directions.len() - 1];
+
+    let mut contour = Vec::with_capacity(left.len() + right.len() + 4);
+    contour.extend(left.iter().copied());
+    contour.extend(cap_vertices(
+        end,
+        *left.last().unwrap(),
+        *right.last().unwrap(),
+        half_width,
+        end_dir,
+        style.line_cap,
+    ));
+    contour.extend(right.iter().rev().copied());
+    contour.extend(cap_vertices(
+        start,
+        right[0],
+        left[0],
+        half_width,
+        -start_dir,
+        style.line_cap,
+    ));
+
+    let mut vertices = contour.into_iter();
+    let first = vertices.next().expect("a stroked dash has at least one vertex");
+    builder.begin(first);
+    for p in vertices {
+        builder.line_to(p);
+    }
+    builder.end(true);
+}
+
+// Consumes a `DashOrGap` stream and strokes each dash polyline (a `Gap` or
+// `ContourBreak` ends the current one) into a closed fill contour,
+// assembling the result into a single `Path` ready for lyon's fill
+// tessellator.
+struct StrokeToFill {
+    style: StrokeStyle,
+    current_dash: Vec<Point>,
+}
+
+impl StrokeToFill {
+    pub fn new(style: StrokeStyle) -> Self {
+        StrokeToFill {
+            style,
+            current_dash: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, item: DashOrGap, builder: &mut Builder) {
+        match item {
+            DashOrGap::Dash { from, to, .. } => {
+                if self.current_dash.is_empty() {
+                    self.current_dash.push(from);
+                }
+                self.current_dash.push(to);
             }
-            _ => {}
+            DashOrGap::Gap { .. } | DashOrGap::ContourBreak => self.flush(builder),
+        }
+    }
+
+    pub fn finish(mut self, mut builder: Builder) -> Path {
+        self.flush(&mut builder);
+        builder.build()
+    }
+
+    fn flush(&mut self, builder: &mut Builder) {
+        if self.current_dash.len() >= 2 {
+            stroke_polyline(&self.current_dash, &self.style, builder);
+        }
+        self.current_dash.clear();
+    }
+}
+
+fn stroke_dashes<I: IntoIterator<Item = DashOrGap>>(dashes: I, style: StrokeStyle) -> Path {
+    let mut stroker = StrokeToFill::new(style);
+    let mut builder = Path::builder();
+    for item in dashes {
+        stroker.push(item, &mut builder);
+    }
+    stroker.finish(builder)
+}
+
+#[cfg(test)]
+mod stroke_tests {
+    use super::*;
+
+    fn max_x(path: &Path) -> f32 {
+        path.iter()
+            .filter_map(|event| match event {
+                PathEvent::Begin { at } => Some(at.x),
+                PathEvent::Line { to, .. } => Some(to.x),
+                _ => None,
+            })
+            .fold(f32::MIN, f32::max)
+    }
+
+    #[test]
+    fn round_cap_bulges_outward_of_dash_end() {
+        let style = StrokeStyle::new(2.0, LineCap::Round, LineJoin::Round);
+        let dashes = vec![DashOrGap::Dash {
+            from: point(0.0, 0.0),
+            to: point(10.0, 0.0),
+            distance: 10.0,
+        }];
+        let path = stroke_dashes(dashes, style);
+        // The round end cap must bulge past the dash tip (x > 10), not
+        // back into the dash (x < 10).
+        assert!(max_x(&path) > 10.0);
+    }
+
+    #[test]
+    fn contour_break_keeps_disjoint_dashes_separate() {
+        // Two unrelated dashes split by a `ContourBreak` must stroke into
+        // two disjoint contours, not get bridged into one merged polyline.
+        let style = StrokeStyle::new(2.0, LineCap::Butt, LineJoin::Bevel);
+        let dashes = vec![
+            DashOrGap::Dash {
+                from: point(0.0, 0.0),
+                to: point(10.0, 0.0),
+                distance: 10.0,
+            },
+            DashOrGap::ContourBreak,
+            DashOrGap::Dash {
+                from: point(100.0, 100.0),
+                to: point(110.0, 100.0),
+                distance: 10.0,
+            },
+        ];
+        let path = stroke_dashes(dashes, style);
+        let contour_count = path
+            .iter()
+            .filter(|event| matches!(event, PathEvent::Begin { .. }))
+            .count();
+        assert_eq!(2, contour_count);
+    }
+
+    #[test]
+    fn degenerate_segment_does_not_produce_nan() {
+        // A duplicate point in the middle of the polyline (e.g. a
+        // zero-length dash offset landing exactly on a boundary) must be
+        // dropped rather than normalized into a NaN direction.
+        let style = StrokeStyle::new(2.0, LineCap::Butt, LineJoin::Bevel);
+        let points = vec![point(0.0, 0.0), point(5.0, 0.0), point(5.0, 0.0), point(10.0, 0.0)];
+        let mut builder = Path::builder();
+        stroke_polyline(&points, &style, &mut builder);
+        let path = builder.build();
+        for event in path.iter() {
+            let at = match event {
+                PathEvent::Begin { at } => at,
+                PathEvent::Line { to, .. } => to,
+                _ => continue,
+            };
+            assert!(at.x.is_finite() && at.y.is_finite());
         }
     }
 }
@@ -392,9 +984,18 @@ fn main() {
     // - DashTo (20,4)-(20,2)
     // - GapTo  (20,2)-(20,1.5)
 
-    let options = DashOptions::new(0.0, vec![1.0, 2.0]);
-    let mut it = FlattenedEventIterator::new(&options);
-    for event in &path {
-        it.next_event(event);
+    let options = DashOptions::new(0.0, vec![2.0, 1.0], 0.1);
+    let dashed_path = DashToPath::from_dashes(FlattenedEventIterator::new(path.iter(), &options));
+    for event in &dashed_path {
+        println!("{:?}", event);
+    }
+
+    let stroke_style = StrokeStyle::new(0.5, LineCap::Round, LineJoin::Round);
+    let stroked_path = stroke_dashes(
+        FlattenedEventIterator::new(path.iter(), &options),
+        stroke_style,
+    );
+    for event in &stroked_path {
+        println!("{:?}", event);
     }
 }